@@ -1,3 +1,6 @@
+use std::cell::{ Cell, RefCell };
+use std::collections::{ HashMap, VecDeque };
+use std::rc::Rc;
 use webutil::prelude::*;
 use webutil::worker::{ Worker, WorkerSender };
 use webutil::channel::{ channel, Receiver };
@@ -8,6 +11,7 @@ use crate::moves::Move;
 use crate::{ Options, Info, AsyncBotState, BotMsg, Thinker, ThinkResult };
 use futures_util::{ select, pin_mut };
 use futures_util::FutureExt;
+use futures_channel::oneshot;
 
 // trait aliases (#41517) would make my life SOOOOO much easier
 // pub trait WebCompatibleEvaluator = where
@@ -15,9 +19,40 @@ use futures_util::FutureExt;
 //     <Self as Evaluator>::Reward: Serialize + DeserializeOwned,
 //     <Self as Evaluator>::Value: Serialize + DeserializeOwned;
 
+/// A message sent back from `bot_thread`, either a finished move or a live analysis update.
+///
+/// Both kinds of message flow over the same worker channel so that analysis updates can't
+/// outrun the moves they were computed alongside. A `Move` reply carries back the request id it
+/// was computed for (`None` for the fire-and-forget `request_next_move` path) so the main thread
+/// can route it to whichever `next_move` call is waiting on it; the reply can't carry the waiting
+/// call's sender directly, since `BotReply` crosses the worker boundary and a
+/// `futures_channel::oneshot::Sender` can't be reconstructed on the other side.
+#[derive(Serialize, serde::Deserialize)]
+enum BotReply {
+    Move(Option<u64>, Move, Info),
+    Analysis(Info)
+}
+
+/// State shared between `Interface` and the background task that drains `bot_thread`'s replies.
+struct Shared {
+    dead: Cell<bool>,
+    // Move results that arrived without a waiting `next_move` call, in arrival order, for
+    // `try_next_move`/`poll_next_move` to pick up.
+    moves: RefCell<VecDeque<(Move, Info)>>,
+    // Senders for in-flight `next_move` calls, keyed by the request id they're waiting on.
+    waiting: RefCell<HashMap<u64, oneshot::Sender<(Move, Info)>>>,
+    // The bot's most recent analysis, alongside a version counter bumped every time it changes.
+    // Each `AnalysisReceiver` remembers the version it last saw, so every subscriber independently
+    // collapses to the latest value instead of the first reader consuming it out from under the
+    // others.
+    analysis: RefCell<Option<Info>>,
+    analysis_version: Cell<u64>
+}
+
 pub struct Interface {
-    dead: bool,
-    worker: Worker<BotMsg, (Move, Info)>
+    worker: Worker<BotMsg, BotReply>,
+    shared: Rc<Shared>,
+    next_request_id: Cell<u64>
 }
 
 impl Interface {
@@ -37,16 +72,59 @@ impl Interface {
         }
 
         let worker = Worker::new(bot_thread, &(board, options, evaluator)).await.unwrap();
+        let shared = Rc::new(Shared {
+            dead: Cell::new(false),
+            moves: RefCell::new(VecDeque::new()),
+            waiting: RefCell::new(HashMap::new()),
+            analysis: RefCell::new(None),
+            analysis_version: Cell::new(0)
+        });
+
+        // Continuously drain `bot_thread`'s replies so a `next_move` call gets woken by the
+        // request id it's waiting on (and a `subscribe_analysis` update is seen) as soon as it
+        // arrives, rather than only when something happens to call `poll_next_move`.
+        spawn_local({
+            let shared = shared.clone();
+            let worker = worker.clone();
+            async move {
+                loop {
+                    match worker.recv().await {
+                        Some(BotReply::Move(Some(id), mv, info)) => {
+                            match shared.waiting.borrow_mut().remove(&id) {
+                                Some(reply) => { reply.send((mv, info)).ok(); }
+                                None => shared.moves.borrow_mut().push_back((mv, info))
+                            }
+                        }
+                        Some(BotReply::Move(None, mv, info)) => {
+                            shared.moves.borrow_mut().push_back((mv, info));
+                        }
+                        Some(BotReply::Analysis(info)) => {
+                            *shared.analysis.borrow_mut() = Some(info);
+                            shared.analysis_version.set(shared.analysis_version.get() + 1);
+                        }
+                        None => {
+                            // The worker closed. Drop every sender still sitting in `waiting` so
+                            // the `next_move` calls awaiting them resolve to `None` instead of
+                            // hanging forever waiting on a reply that can now never arrive.
+                            shared.dead.set(true);
+                            shared.waiting.borrow_mut().clear();
+                            break;
+                        }
+                    }
+                }
+            }
+        });
 
         Interface {
-            dead: false,
-            worker
+            worker,
+            shared,
+            next_request_id: Cell::new(0)
         }
     }
 
     /// Returns true if all possible piece placement sequences result in death.
     pub fn is_dead(&self) -> bool {
-        self.dead
+        self.shared.dead.get()
     }
 
     /// Request the bot to provide a move as soon as possible.
@@ -67,40 +145,105 @@ impl Interface {
     /// Once a move is chosen, the bot will update its internal state to the result of the piece
     /// being placed correctly and the move will become available by calling `poll_next_move`.
     pub fn request_next_move(&mut self, incoming: u32) {
-        if self.worker.send(&BotMsg::NextMove(incoming)).is_err() {
-            self.dead = true;
+        if self.worker.send(&BotMsg::NextMove(incoming, None)).is_err() {
+            self.shared.dead.set(true);
         }
     }
 
+    /// Requests a move and waits for the bot to provide it, without polling.
+    ///
+    /// This behaves like `request_next_move` followed by repeatedly calling `poll_next_move`,
+    /// except the chosen move is handed to this call through a one-shot completion channel
+    /// instead of being queued for `poll_next_move`, so it doesn't have to be polled every frame.
+    /// The move still travels back over the worker's reply channel, tagged with this call's
+    /// request id, and the background task started in `launch` routes it to the one-shot channel. This resolves to `None` if the bot thread dies before it can provide a
+    /// move; use this instead of `poll_next_move` when you're driving the bot from an async event
+    /// loop rather than a per-frame game loop.
+    pub async fn next_move(&mut self, incoming: u32) -> Option<(Move, Info)> {
+        let id = self.next_request_id.get();
+        self.next_request_id.set(id + 1);
+        let (send, recv) = oneshot::channel();
+        self.shared.waiting.borrow_mut().insert(id, send);
+        if self.worker.send(&BotMsg::NextMove(incoming, Some(id))).is_err() {
+            self.shared.dead.set(true);
+            self.shared.waiting.borrow_mut().remove(&id);
+            return None;
+        }
+        recv.await.ok()
+    }
+
     /// Checks to see if the bot has provided the previously requested move yet.
-    /// 
+    ///
+    /// Unlike `poll_next_move`, this distinguishes "no move yet" from "the bot thread is gone"
+    /// via `MoveError`, so you don't need to separately check `is_dead` after every poll.
+    ///
     /// The returned move contains both a path and the expected location of the placed piece. The
     /// returned path is reasonably good, but you might want to use your own pathfinder to, for
     /// example, exploit movement intricacies in the game you're playing.
-    /// 
+    ///
+    /// If the piece couldn't be placed in the expected location, you must call `reset` to reset the
+    /// game field, back-to-back status, and combo values.
+    pub fn try_next_move(&mut self) -> Result<(Move, Info), MoveError> {
+        if let Some(result) = self.shared.moves.borrow_mut().pop_front() {
+            Ok(result)
+        } else if self.shared.dead.get() {
+            Err(MoveError::Closed)
+        } else {
+            Err(MoveError::Empty)
+        }
+    }
+
+    /// Checks to see if the bot has provided the previously requested move yet.
+    ///
+    /// This is a thin wrapper around `try_next_move` kept for compatibility; it collapses the
+    /// "not ready yet" and "bot thread is gone" cases back down to `None`. Prefer `try_next_move`
+    /// if you want to tell those two apart.
+    ///
+    /// The returned move contains both a path and the expected location of the placed piece. The
+    /// returned path is reasonably good, but you might want to use your own pathfinder to, for
+    /// example, exploit movement intricacies in the game you're playing.
+    ///
     /// If the piece couldn't be placed in the expected location, you must call `reset` to reset the
     /// game field, back-to-back status, and combo values.
     pub fn poll_next_move(&mut self) -> Option<(Move, Info)> {
-        self.worker.try_recv()
+        self.try_next_move().ok()
+    }
+
+    /// Subscribes to the bot's continuously-improving best line, independent of requesting a move.
+    ///
+    /// The returned `AnalysisReceiver` always yields the most recent `Info` the bot has published
+    /// since it was last read, collapsing any updates that happened in between rather than
+    /// queueing them, similar to a `watch` channel. Each subscriber tracks its own read position,
+    /// so multiple `AnalysisReceiver`s can coexist without stealing updates from one another. This
+    /// lets a visualizer render the bot "thinking" in real time without interfering with
+    /// `request_next_move`/`poll_next_move`, and without the bot's continuous stream of
+    /// improvements piling up in memory if nobody's reading them. Updates are pulled off the
+    /// worker channel by a background task started in `launch`, so this stays current even if the
+    /// interface is never otherwise polled.
+    pub fn subscribe_analysis(&mut self) -> AnalysisReceiver {
+        AnalysisReceiver {
+            shared: self.shared.clone(),
+            seen: 0
+        }
     }
 
     /// Adds a new piece to the end of the queue.
-    /// 
+    ///
     /// If speculation is enabled, the piece *must* be in the bag. For example, if in the current
     /// bag you've provided the sequence IJOZT, then the next time you call this function you can
     /// only provide either an L or an S piece.
     pub fn add_next_piece(&mut self, piece: Piece) {
         if self.worker.send(&BotMsg::NewPiece(piece)).is_err() {
-            self.dead = true;
+            self.shared.dead.set(true);
         }
     }
 
     /// Resets the playfield, back-to-back status, and combo count.
-    /// 
+    ///
     /// This should only be used when garbage is received or when your client could not place the
     /// piece in the correct position for some reason (e.g. 15 move rule), since this forces the
     /// bot to throw away previous computations.
-    /// 
+    ///
     /// Note: combo is not the same as the displayed combo in guideline games. Here, it is the
     /// number of consecutive line clears achieved. So, generally speaking, if "x Combo" appears
     /// on the screen, you need to use x+1 here.
@@ -108,22 +251,125 @@ impl Interface {
         if self.worker.send(&BotMsg::Reset {
             field, b2b: b2b_active, combo
         }).is_err() {
-            self.dead = true;
+            self.shared.dead.set(true);
         }
     }
 
     /// Specifies a line that Cold Clear should analyze before making any moves.
     pub fn force_analysis_line(&mut self, path: Vec<FallingPiece>) {
         if self.worker.send(&BotMsg::ForceAnalysisLine(path)).is_err() {
-            self.dead = true;
+            self.shared.dead.set(true);
+        }
+    }
+}
+
+/// The reason `try_next_move` couldn't immediately return a move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    /// The bot hasn't chosen a move yet; keep waiting and poll again later.
+    Empty,
+    /// The bot thread has closed, so it will never provide a move.
+    Closed
+}
+
+/// A handle to the bot's live analysis, obtained from `Interface::subscribe_analysis`.
+pub struct AnalysisReceiver {
+    shared: Rc<Shared>,
+    // The `analysis_version` this receiver has already handed out.
+    seen: u64
+}
+
+impl AnalysisReceiver {
+    /// Returns the bot's latest analysis, or `None` if it hasn't changed since the last read.
+    pub fn recv(&mut self) -> Option<Info> {
+        let version = self.shared.analysis_version.get();
+        if version == self.seen {
+            None
+        } else {
+            self.seen = version;
+            self.shared.analysis.borrow().clone()
+        }
+    }
+}
+
+// Extra slack beyond one job per worker thread, so a worker can start on its next job immediately
+// after finishing instead of waiting on `bot_thread` to acquire the freed permit and dispatch a job.
+const THINK_QUEUE_SLACK: usize = 2;
+
+/// A counting semaphore bounding how many `Thinker` jobs may be in flight at once.
+///
+/// This caps the size of `think_send`/`thinker_recv` (and the memory each pending `Thinker` holds)
+/// to roughly `threads + THINK_QUEUE_SLACK`, rather than letting `state.think` queue up stale jobs
+/// faster than the worker pool can drain them.
+struct Semaphore {
+    permits: Cell<usize>,
+    wakers: RefCell<VecDeque<std::task::Waker>>
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Rc<Self> {
+        Rc::new(Semaphore {
+            permits: Cell::new(permits),
+            wakers: RefCell::new(VecDeque::new())
+        })
+    }
+
+    fn has_permit(&self) -> bool {
+        self.permits.get() > 0
+    }
+
+    fn try_acquire(&self) -> bool {
+        if self.has_permit() {
+            self.permits.set(self.permits.get() - 1);
+            true
+        } else {
+            false
+        }
+    }
+
+    async fn acquire(self: &Rc<Self>) -> PermitGuard {
+        let this = self.clone();
+        futures_util::future::poll_fn(move |cx| {
+            if this.try_acquire() {
+                std::task::Poll::Ready(())
+            } else {
+                // Only register once per waiter: re-polling a still-pending `acquire` would
+                // otherwise enqueue another waker for the same waiter, and `release` would later
+                // burn a wakeup on an already-satisfied one instead of waking the next waiter.
+                let mut wakers = this.wakers.borrow_mut();
+                if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    wakers.push_back(cx.waker().clone());
+                }
+                std::task::Poll::Pending
+            }
+        }).await;
+        PermitGuard(self.clone())
+    }
+
+    fn release(&self) {
+        self.permits.set(self.permits.get() + 1);
+        if let Some(waker) = self.wakers.borrow_mut().pop_front() {
+            waker.wake();
         }
     }
 }
 
+/// Holds a `Semaphore` permit for as long as a dispatched `Thinker` job is in flight, releasing it
+/// on drop. The permit is given back when the thinker task finishes handling the job, whether or
+/// not the job's worker returned a result, so a worker whose `recv` ends without a result doesn't
+/// leak it. A job whose worker never replies keeps its permit for as long as that `recv` waits.
+struct PermitGuard(Rc<Semaphore>);
+
+impl Drop for PermitGuard {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
 fn bot_thread<E>(
     (board, options, eval): (Board, Options, E),
     recv: Receiver<BotMsg>,
-    send: WorkerSender<(Move, Info)>
+    send: WorkerSender<BotReply>
 ) where
     E: Evaluator + Clone + Serialize + DeserializeOwned + 'static,
     E::Value: Serialize + DeserializeOwned,
@@ -131,26 +377,58 @@ fn bot_thread<E>(
 {
     spawn_local(async move {
         let (result_send, think_recv) = channel::<ThinkResult<E>>();
-        let (think_send, thinker_recv) = channel::<Thinker<E>>();
+        let (think_send, thinker_recv) = channel::<(Thinker<E>, PermitGuard)>();
+        let permits = Semaphore::new(options.threads as usize + THINK_QUEUE_SLACK);
         // spawn thinker workers
         for _ in 0..options.threads {
             let result_send = result_send.clone();
             let thinker_recv = thinker_recv.clone();
             spawn_local(async move {
                 let think_worker = Worker::new(thinker, &()).await.unwrap();
-                while let Some(thinker) = thinker_recv.recv().await {
+                while let Some((thinker, _permit)) = thinker_recv.recv().await {
                     think_worker.send(&thinker).unwrap();
-                    result_send.send(think_worker.recv().await).ok().unwrap();
+                    let result = think_worker.recv().await;
+                    // `_permit` is dropped at the end of this iteration regardless of whether
+                    // `recv` above actually produced a result, so a worker that dies mid-job still
+                    // gives its permit back instead of leaking it.
+                    result_send.send(result).ok().unwrap();
                 }
             });
         }
 
         let mut state = AsyncBotState::new(board, options, eval);
+        // Request ids for in-flight `NextMove` requests, in the order they were requested. `None`
+        // means the request came from `request_next_move`, which just wants the move on the
+        // generic result channel like before; `Some(id)` is echoed back in `BotReply::Move` so the
+        // main thread can route the move to the `next_move` call waiting on it, since a
+        // `BotReply` crosses the worker boundary and can't carry that call's sender directly.
+        //
+        // Invariant this queue relies on: `state.think`'s callback fires exactly once per queued
+        // `NextMove` message, in the same order the messages were sent, so popping the front of
+        // this queue inside the callback always pairs the right id with the right move. If
+        // `AsyncBotState` can ever coalesce two `NextMove`s into a single emitted move, this FIFO
+        // pairing breaks and the extra id's `next_move` call would hang; it isn't re-derived here
+        // because `AsyncBotState::message`/`think` live outside this file.
+        let mut move_replies: VecDeque<Option<u64>> = VecDeque::new();
 
         while !state.is_dead() {
-            let (new_thinks, _) = state.think(|mv, info| send.send(&(mv, info)));
+            // `state.think`'s second return value is assumed to be `Option<Info>` (the root's
+            // latest best line when it changed), matching `think_done` below; this isn't visible
+            // to verify here since `AsyncBotState::think` is defined outside this file.
+            let (new_thinks, improved) = state.think(|mv, info| {
+                let id = move_replies.pop_front().flatten();
+                send.send(&BotReply::Move(id, mv, info))
+            });
+            // Publish the bot's improved best line for anyone subscribed via `subscribe_analysis`.
+            if let Some(info) = improved {
+                send.send(&BotReply::Analysis(info));
+            }
+            // `permits.acquire()` backpressures job generation to the worker pool's actual
+            // capacity; `state.think` itself runs every iteration regardless of permits so that a
+            // `think_done` below isn't skipped just because the worker pool is momentarily full.
             for thinker in new_thinks {
-                think_send.send(thinker).ok().unwrap();
+                let permit = permits.acquire().await;
+                think_send.send((thinker, permit)).ok().unwrap();
             }
 
             let msg = recv.recv().fuse();
@@ -158,10 +436,21 @@ fn bot_thread<E>(
             pin_mut!(msg, think);
             select! {
                 msg = msg => match msg {
+                    Some(BotMsg::NextMove(incoming, id)) => {
+                        move_replies.push_back(id);
+                        state.message(BotMsg::NextMove(incoming, None));
+                    }
                     Some(msg) => state.message(msg),
                     None => break
                 },
-                think = think => state.think_done(think.unwrap())
+                think = think => {
+                    // `think_done` is where completed worker results actually get folded into the
+                    // search tree and the root's best line gets refined, so this is just as much a
+                    // source of analysis updates as `state.think`'s `improved` return above.
+                    if let Some(info) = state.think_done(think.unwrap()) {
+                        send.send(&BotReply::Analysis(info));
+                    }
+                }
             }
         }
     });